@@ -0,0 +1,8 @@
+//! Value deserialization.
+
+mod value;
+mod value_ref;
+
+pub use self::value::{Error, ErrorCode, DEFAULT_MAX_DEPTH, DEFAULT_MAX_PREALLOC, read_value,
+                       read_value_with_limit, read_value_with_limits};
+pub use self::value_ref::read_value_ref;
@@ -1,6 +1,8 @@
+use std::cmp;
 use std::error;
 use std::fmt::{self, Display, Formatter};
 use std::io::{self, ErrorKind, Read};
+use std::mem;
 
 use rmp::Marker;
 use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
@@ -9,95 +11,212 @@ use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_
 
 use {Value, Utf8String};
 
-/// This type represents all possible errors that can occur when deserializing a value.
+/// The reserved extension type used by the MessagePack timestamp extension.
+pub(crate) const EXT_TYPE_TIMESTAMP: i8 = -1;
+
+/// The default maximum nesting depth used by `read_value`.
+///
+/// Chosen to comfortably handle realistically nested data while still failing well before the
+/// stack gives out.
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// The default ceiling, in bytes, on how much memory `read_value` will reserve up front for a
+/// single str/bin/ext/array/map length prefix.
+///
+/// Bodies longer than this are read incrementally in chunks of this size, so a hostile length
+/// prefix can never force an allocation more than one chunk larger than the data actually present
+/// in the stream.
+pub const DEFAULT_MAX_PREALLOC: usize = 64 * 1024;
+
+/// The specific kind of failure behind an `Error`.
 #[derive(Debug)]
-pub enum Error {
+pub enum ErrorCode {
     /// Error while reading marker byte.
     InvalidMarkerRead(io::Error),
     /// Error while reading data.
     InvalidDataRead(io::Error),
+    /// The nesting of arrays/maps in the input exceeded the configured maximum depth.
+    DepthLimitExceeded,
+    /// The payload of a timestamp extension (type `-1`) didn't match any of the three timestamp
+    /// wire formats (4, 8 or 12 bytes).
+    InvalidTimestamp,
 }
 
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
+impl ErrorCode {
+    fn kind(&self) -> ErrorKind {
         match *self {
-            Error::InvalidMarkerRead(ref err) => err.kind(),
-            Error::InvalidDataRead(ref err) => err.kind(),
+            ErrorCode::InvalidMarkerRead(ref err) => err.kind(),
+            ErrorCode::InvalidDataRead(ref err) => err.kind(),
+            ErrorCode::DepthLimitExceeded => ErrorKind::Other,
+            ErrorCode::InvalidTimestamp => ErrorKind::Other,
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ErrorCode::InvalidMarkerRead(ref err) => {
+                write!(fmt, "I/O error while reading marker byte: {}", err)
+            }
+            ErrorCode::InvalidDataRead(ref err) => {
+                write!(fmt, "I/O error while reading non-marker bytes: {}", err)
+            }
+            ErrorCode::DepthLimitExceeded => {
+                write!(fmt, "depth limit exceeded while decoding a nested container")
+            }
+            ErrorCode::InvalidTimestamp => {
+                write!(fmt, "invalid timestamp extension payload")
+            }
         }
     }
 }
 
+/// This type represents all possible errors that can occur when deserializing a value.
+///
+/// In addition to the underlying `ErrorCode`, it carries the byte offset into the input at which
+/// the failure occurred, which is useful when parsing multi-value streams or debugging malformed
+/// frames.
+#[derive(Debug)]
+pub struct Error {
+    code: ErrorCode,
+    position: u64,
+}
+
+impl Error {
+    pub(crate) fn new(code: ErrorCode) -> Error {
+        Error { code: code, position: 0 }
+    }
+
+    pub(crate) fn at(mut self, position: u64) -> Error {
+        self.position = position;
+        self
+    }
+
+    /// Returns the specific kind of decoding failure that occurred.
+    pub fn code(&self) -> &ErrorCode {
+        &self.code
+    }
+
+    /// Returns the number of bytes consumed from the reader before this error occurred.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.code.kind()
+    }
+}
+
 impl error::Error for Error {
     fn description(&self) -> &str {
-        match *self {
-            Error::InvalidMarkerRead(..) => "I/O error while reading marker byte",
-            Error::InvalidDataRead(..) => "I/O error while reading non-marker bytes",
+        match self.code {
+            ErrorCode::InvalidMarkerRead(..) => "I/O error while reading marker byte",
+            ErrorCode::InvalidDataRead(..) => "I/O error while reading non-marker bytes",
+            ErrorCode::DepthLimitExceeded => "depth limit exceeded while decoding a nested container",
+            ErrorCode::InvalidTimestamp => "invalid timestamp extension payload",
         }
     }
 
     fn cause(&self) -> Option<&error::Error> {
-        match *self {
-            Error::InvalidMarkerRead(ref err) => Some(err),
-            Error::InvalidDataRead(ref err) => Some(err),
+        match self.code {
+            ErrorCode::InvalidMarkerRead(ref err) => Some(err),
+            ErrorCode::InvalidDataRead(ref err) => Some(err),
+            _ => None,
         }
     }
 }
 
 impl Display for Error {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
-        match *self {
-            Error::InvalidMarkerRead(ref err) => {
-                write!(fmt, "I/O error while reading marker byte: {}", err)
-            }
-            Error::InvalidDataRead(ref err) => {
-                write!(fmt, "I/O error while reading non-marker bytes: {}", err)
-            }
-        }
+        write!(fmt, "{} (at byte offset {})", self.code, self.position)
     }
 }
 
 impl From<MarkerReadError> for Error {
     fn from(err: MarkerReadError) -> Error {
-        Error::InvalidMarkerRead(err.0)
+        Error::new(ErrorCode::InvalidMarkerRead(err.0))
     }
 }
 
 impl From<ValueReadError> for Error {
     fn from(err: ValueReadError) -> Error {
-        match err {
-            ValueReadError::InvalidMarkerRead(err) => Error::InvalidMarkerRead(err),
-            ValueReadError::InvalidDataRead(err) => Error::InvalidDataRead(err),
-            ValueReadError::TypeMismatch(..) => {
-                Error::InvalidMarkerRead(io::Error::new(ErrorKind::Other, "type mismatch"))
+        let code = match err {
+            ValueReadError::InvalidMarkerRead(err) => ErrorCode::InvalidMarkerRead(err),
+            ValueReadError::InvalidDataRead(err) => ErrorCode::InvalidDataRead(err),
+            // Every call site here reads raw data for a marker that read_marker has already
+            // matched, so rmp never actually produces this variant from this decode path; fold
+            // it into InvalidDataRead rather than carrying a Marker that can't occur in practice.
+            ValueReadError::TypeMismatch(marker) => {
+                let msg = format!("unexpected marker {:?}", marker);
+                ErrorCode::InvalidDataRead(io::Error::new(ErrorKind::Other, msg))
             }
-        }
+        };
+
+        Error::new(code)
+    }
+}
+
+/// Wraps a reader, counting the number of bytes successfully read through it so that a decoding
+/// failure can be attributed to a byte offset in the input.
+struct OffsetReader<'a, R: 'a> {
+    inner: &'a mut R,
+    position: u64,
+}
+
+impl<'a, R: Read + 'a> OffsetReader<'a, R> {
+    fn new(inner: &'a mut R) -> OffsetReader<'a, R> {
+        OffsetReader { inner: inner, position: 0 }
+    }
+
+    fn position(&self) -> u64 {
+        self.position
     }
 }
 
-fn read_array_data<R: Read>(rd: &mut R, mut len: usize) -> Result<Vec<Value>, Error> {
-    let mut vec = Vec::with_capacity(len);
+impl<'a, R: Read + 'a> Read for OffsetReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+pub(crate) fn next_depth(depth: usize, max_depth: usize) -> Result<usize, Error> {
+    if depth >= max_depth {
+        return Err(Error::new(ErrorCode::DepthLimitExceeded));
+    }
+
+    Ok(depth + 1)
+}
+
+fn read_array_data<R: Read>(rd: &mut R, mut len: usize, depth: usize, max_depth: usize, max_prealloc: usize) -> Result<Vec<Value>, Error> {
+    let cap = cmp::min(len, max_prealloc / cmp::max(1, mem::size_of::<Value>()));
+    let mut vec = Vec::with_capacity(cap);
 
     while len > 0 {
-        vec.push(read_value(rd)?);
+        vec.push(read_value_with_depth(rd, depth, max_depth, max_prealloc)?);
         len -= 1;
     }
 
     Ok(vec)
 }
 
-fn read_map_data<R: Read>(rd: &mut R, mut len: usize) -> Result<Vec<(Value, Value)>, Error> {
-    let mut vec = Vec::with_capacity(len);
+fn read_map_data<R: Read>(rd: &mut R, mut len: usize, depth: usize, max_depth: usize, max_prealloc: usize) -> Result<Vec<(Value, Value)>, Error> {
+    let cap = cmp::min(len, max_prealloc / cmp::max(1, mem::size_of::<(Value, Value)>()));
+    let mut vec = Vec::with_capacity(cap);
 
     while len > 0 {
-        vec.push((read_value(rd)?, read_value(rd)?));
+        vec.push((read_value_with_depth(rd, depth, max_depth, max_prealloc)?,
+                   read_value_with_depth(rd, depth, max_depth, max_prealloc)?));
         len -= 1;
     }
 
     Ok(vec)
 }
 
-fn read_str_data<R: Read>(rd: &mut R, len: usize) -> Result<Utf8String, Error> {
-    match String::from_utf8(read_bin_data(rd, len)?) {
+fn read_str_data<R: Read>(rd: &mut R, len: usize, max_prealloc: usize) -> Result<Utf8String, Error> {
+    match String::from_utf8(read_bin_data(rd, len, max_prealloc)?) {
         Ok(s) => Ok(Utf8String::from(s)),
         Err(err) => {
             let e = err.utf8_error();
@@ -109,30 +228,135 @@ fn read_str_data<R: Read>(rd: &mut R, len: usize) -> Result<Utf8String, Error> {
     }
 }
 
-fn read_bin_data<R: Read>(rd: &mut R, len: usize) -> Result<Vec<u8>, Error> {
-    let mut buf = Vec::with_capacity(len);
-    buf.resize(len as usize, 0u8);
-    rd.read_exact(&mut buf[..]).map_err(Error::InvalidDataRead)?;
+/// Reads exactly `len` bytes from `rd`, growing the buffer incrementally in chunks of at most
+/// `max_prealloc` bytes rather than reserving `len` up front, so an attacker-controlled length
+/// prefix cannot force a single oversized allocation ahead of the data actually arriving.
+fn read_bin_data<R: Read>(rd: &mut R, len: usize, max_prealloc: usize) -> Result<Vec<u8>, Error> {
+    // A `max_prealloc` of 0 would otherwise make every chunk 0 bytes long and loop forever.
+    let max_prealloc = cmp::max(1, max_prealloc);
+    let mut buf = Vec::with_capacity(cmp::min(len, max_prealloc));
+    let mut remaining = len;
+
+    while remaining > 0 {
+        let chunk_len = cmp::min(remaining, max_prealloc);
+        let start = buf.len();
+        buf.resize(start + chunk_len, 0u8);
+        rd.read_exact(&mut buf[start..]).map_err(|err| Error::new(ErrorCode::InvalidDataRead(err)))?;
+        remaining -= chunk_len;
+    }
 
     Ok(buf)
 }
 
-fn read_ext_body<R: Read>(rd: &mut R, len: usize) -> Result<(i8, Vec<u8>), Error> {
+fn read_ext_body<R: Read>(rd: &mut R, len: usize, max_prealloc: usize) -> Result<(i8, Vec<u8>), Error> {
     let ty = read_data_i8(rd)?;
-    let vec = read_bin_data(rd, len)?;
+    let vec = read_bin_data(rd, len, max_prealloc)?;
 
     Ok((ty, vec))
 }
 
+/// Turns a decoded ext type/payload pair into a `Value`, special-casing the reserved timestamp
+/// extension (type `-1`) into `Value::Timestamp` instead of leaving it as an opaque `Value::Ext`.
+fn ext_to_value(ty: i8, data: Vec<u8>) -> Result<Value, Error> {
+    if ty == EXT_TYPE_TIMESTAMP {
+        let (seconds, nanos) = read_timestamp(&data)?;
+        Ok(Value::Timestamp { seconds: seconds, nanos: nanos })
+    } else {
+        Ok(Value::Ext(ty, data))
+    }
+}
+
+/// Parses the payload of a timestamp extension into `(seconds, nanos)`, in one of its three wire
+/// formats:
+///
+/// - timestamp 32 (4 bytes): a big-endian `u32` of seconds, with zero nanoseconds.
+/// - timestamp 64 (8 bytes): a big-endian `u64` packing a 30-bit nanosecond count in the upper
+///   bits and a 34-bit second count in the lower bits.
+/// - timestamp 96 (12 bytes): a big-endian `u32` of nanoseconds followed by a big-endian `i64` of
+///   seconds.
+pub(crate) fn read_timestamp(data: &[u8]) -> Result<(i64, u32), Error> {
+    match data.len() {
+        4 => Ok((read_be_u32(data) as i64, 0)),
+        8 => {
+            let value = read_be_u64(data);
+            let nanos = (value >> 34) as u32;
+            let seconds = (value & 0x3_ffff_ffff) as i64;
+            Ok((seconds, nanos))
+        }
+        12 => {
+            let nanos = read_be_u32(&data[..4]);
+            let seconds = read_be_u64(&data[4..]) as i64;
+            Ok((seconds, nanos))
+        }
+        _ => Err(Error::new(ErrorCode::InvalidTimestamp)),
+    }
+}
+
+fn read_be_u32(data: &[u8]) -> u32 {
+    (data[0] as u32) << 24 | (data[1] as u32) << 16 | (data[2] as u32) << 8 | (data[3] as u32)
+}
+
+fn read_be_u64(data: &[u8]) -> u64 {
+    data.iter().fold(0u64, |acc, &byte| (acc << 8) | byte as u64)
+}
+
 /// Attempts to read bytes from the given reader and interpret them as a `Value`.
 ///
 /// # Errors
 ///
 /// This function will return `Error` on any I/O error while either reading or decoding a `Value`.
 /// All instances of `ErrorKind::Interrupted` are handled by this function and the underlying
-/// operation is retried.
+/// operation is retried. The returned `Error` carries the byte offset of the failure, available
+/// via `Error::position`.
 pub fn read_value<R>(rd: &mut R) -> Result<Value, Error>
     where R: Read
+{
+    read_value_with_limits(rd, DEFAULT_MAX_DEPTH, DEFAULT_MAX_PREALLOC)
+}
+
+/// Attempts to read bytes from the given reader and interpret them as a `Value`, failing with
+/// `ErrorCode::DepthLimitExceeded` if arrays/maps are nested more than `max_depth` levels deep.
+///
+/// This is a safer entry point than `read_value` when decoding untrusted input, since it bounds
+/// the recursion used to walk nested containers and so cannot be used to overflow the stack.
+///
+/// # Errors
+///
+/// This function will return `Error` on any I/O error while either reading or decoding a `Value`,
+/// or `ErrorCode::DepthLimitExceeded` on an attempt to enter more than `max_depth` nested
+/// containers.
+pub fn read_value_with_limit<R>(rd: &mut R, max_depth: usize) -> Result<Value, Error>
+    where R: Read
+{
+    read_value_with_limits(rd, max_depth, DEFAULT_MAX_PREALLOC)
+}
+
+/// Attempts to read bytes from the given reader and interpret them as a `Value`, bounding both
+/// the container nesting depth (`max_depth`) and the amount of memory reserved up front for any
+/// single str/bin/ext/array/map body (`max_prealloc`).
+///
+/// This is the entry point to use when decoding frames from an untrusted source: neither a
+/// pathologically nested container nor an oversized length prefix can do more damage than
+/// `max_depth` levels of nesting and `max_prealloc` bytes of speculative allocation per
+/// str/bin/ext/array/map body, regardless of what the length prefixes in the stream claim.
+///
+/// # Errors
+///
+/// This function will return `Error` on any I/O error while either reading or decoding a `Value`,
+/// or `ErrorCode::DepthLimitExceeded` on an attempt to enter more than `max_depth` nested
+/// containers. The
+/// returned `Error` carries the byte offset consumed from `rd` before the failure, available via
+/// `Error::position`.
+pub fn read_value_with_limits<R>(rd: &mut R, max_depth: usize, max_prealloc: usize) -> Result<Value, Error>
+    where R: Read
+{
+    let mut rd = OffsetReader::new(rd);
+
+    read_value_with_depth(&mut rd, 0, max_depth, max_prealloc).map_err(|err| err.at(rd.position()))
+}
+
+fn read_value_with_depth<R>(rd: &mut R, depth: usize, max_depth: usize, max_prealloc: usize) -> Result<Value, Error>
+    where R: Read
 {
     let val = match read_marker(rd)? {
         Marker::Null => Value::Nil,
@@ -151,109 +375,214 @@ pub fn read_value<R>(rd: &mut R) -> Result<Value, Error>
         Marker::F32 => Value::F32(read_data_f32(rd)?),
         Marker::F64 => Value::F64(read_data_f64(rd)?),
         Marker::FixStr(len) => {
-            let res = read_str_data(rd, len as usize)?;
+            let res = read_str_data(rd, len as usize, max_prealloc)?;
             Value::String(res)
         }
         Marker::Str8 => {
             let len = read_data_u8(rd)?;
-            let res = read_str_data(rd, len as usize)?;
+            let res = read_str_data(rd, len as usize, max_prealloc)?;
             Value::String(res)
         }
         Marker::Str16 => {
             let len = read_data_u16(rd)?;
-            let res = read_str_data(rd, len as usize)?;
+            let res = read_str_data(rd, len as usize, max_prealloc)?;
             Value::String(res)
         }
         Marker::Str32 => {
             let len = read_data_u32(rd)?;
-            let res = read_str_data(rd, len as usize)?;
+            let res = read_str_data(rd, len as usize, max_prealloc)?;
             Value::String(res)
         }
         Marker::FixArray(len) => {
-            let vec = read_array_data(rd, len as usize)?;
+            let vec = read_array_data(rd, len as usize, next_depth(depth, max_depth)?, max_depth, max_prealloc)?;
             Value::Array(vec)
         }
         Marker::Array16 => {
             let len = read_data_u16(rd)?;
-            let vec = read_array_data(rd, len as usize)?;
+            let vec = read_array_data(rd, len as usize, next_depth(depth, max_depth)?, max_depth, max_prealloc)?;
             Value::Array(vec)
         }
         Marker::Array32 => {
             let len = read_data_u32(rd)?;
-            let vec = read_array_data(rd, len as usize)?;
+            let vec = read_array_data(rd, len as usize, next_depth(depth, max_depth)?, max_depth, max_prealloc)?;
             Value::Array(vec)
         }
         Marker::FixMap(len) => {
-            let map = read_map_data(rd, len as usize)?;
+            let map = read_map_data(rd, len as usize, next_depth(depth, max_depth)?, max_depth, max_prealloc)?;
             Value::Map(map)
         }
         Marker::Map16 => {
             let len = read_data_u16(rd)?;
-            let map = read_map_data(rd, len as usize)?;
+            let map = read_map_data(rd, len as usize, next_depth(depth, max_depth)?, max_depth, max_prealloc)?;
             Value::Map(map)
         }
         Marker::Map32 => {
             let len = read_data_u32(rd)?;
-            let map = read_map_data(rd, len as usize)?;
+            let map = read_map_data(rd, len as usize, next_depth(depth, max_depth)?, max_depth, max_prealloc)?;
             Value::Map(map)
         }
         Marker::Bin8 => {
             let len = read_data_u8(rd)?;
-            let vec = read_bin_data(rd, len as usize)?;
+            let vec = read_bin_data(rd, len as usize, max_prealloc)?;
             Value::Binary(vec)
         }
         Marker::Bin16 => {
             let len = read_data_u16(rd)?;
-            let vec = read_bin_data(rd, len as usize)?;
+            let vec = read_bin_data(rd, len as usize, max_prealloc)?;
             Value::Binary(vec)
         }
         Marker::Bin32 => {
             let len = read_data_u32(rd)?;
-            let vec = read_bin_data(rd, len as usize)?;
+            let vec = read_bin_data(rd, len as usize, max_prealloc)?;
             Value::Binary(vec)
         }
         Marker::FixExt1 => {
             let len = 1 as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::FixExt2 => {
             let len = 2 as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::FixExt4 => {
             let len = 4 as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::FixExt8 => {
             let len = 8 as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::FixExt16 => {
             let len = 16 as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::Ext8 => {
             let len = read_data_u8(rd)? as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::Ext16 => {
             let len = read_data_u16(rd)? as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::Ext32 => {
             let len = read_data_u32(rd)? as usize;
-            let (ty, vec) = read_ext_body(rd, len)?;
-            Value::Ext(ty, vec)
+            let (ty, vec) = read_ext_body(rd, len, max_prealloc)?;
+            ext_to_value(ty, vec)?
         }
         Marker::Reserved => Value::Nil,
     };
 
     Ok(val)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixarray(len: u8) -> u8 {
+        0x90 | len
+    }
+
+    #[test]
+    fn read_value_with_limit_trips_depth_limit_on_deeply_nested_arrays() {
+        // 4 singly-nested, empty FixArrays: [[[[]]]]
+        let buf = [fixarray(1), fixarray(1), fixarray(1), fixarray(0)];
+
+        let res = read_value_with_limit(&mut &buf[..], 3);
+
+        match res {
+            Err(ref err) => assert_matches_depth_limit(err),
+            Ok(ref val) => panic!("expected depth limit error, got {:?}", val),
+        }
+    }
+
+    #[test]
+    fn read_value_with_limit_allows_nesting_within_the_limit() {
+        let buf = [fixarray(1), fixarray(1), fixarray(0)];
+
+        let val = read_value_with_limit(&mut &buf[..], 3).unwrap();
+
+        assert_eq!(Value::Array(vec![Value::Array(vec![Value::Array(vec![])])]), val);
+    }
+
+    fn assert_matches_depth_limit(err: &Error) {
+        match *err.code() {
+            ErrorCode::DepthLimitExceeded => {}
+            ref other => panic!("expected DepthLimitExceeded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn read_bin_data_rejects_an_oversized_length_prefix_without_hanging() {
+        // Bin32 claims a 4 GiB body, but only a handful of bytes actually follow; the chunked
+        // read must fail on the short read rather than trying to allocate 4 GiB up front.
+        let mut buf = vec![0xc6, 0xff, 0xff, 0xff, 0xff];
+        buf.extend_from_slice(&[0u8; 4]);
+
+        let res = read_value_with_limits(&mut &buf[..], DEFAULT_MAX_DEPTH, 1024);
+
+        match res {
+            Err(ref err) => match *err.code() {
+                ErrorCode::InvalidDataRead(..) => {}
+                ref other => panic!("expected InvalidDataRead, got {:?}", other),
+            },
+            Ok(ref val) => panic!("expected a read error, got {:?}", val),
+        }
+    }
+
+    #[test]
+    fn read_bin_data_does_not_hang_when_max_prealloc_is_zero() {
+        let buf = [0xc4, 0x02, 0xaa, 0xbb]; // Bin8, len 2, payload
+
+        let val = read_value_with_limits(&mut &buf[..], DEFAULT_MAX_DEPTH, 0).unwrap();
+
+        assert_eq!(Value::Binary(vec![0xaa, 0xbb]), val);
+    }
+
+    #[test]
+    fn read_timestamp_decodes_all_three_wire_formats() {
+        assert_eq!((1, 0), read_timestamp(&[0x00, 0x00, 0x00, 0x01]).unwrap());
+
+        // timestamp 64: 1 nanosecond, 2 seconds, packed as (nanos << 34) | seconds.
+        let packed: u64 = (1u64 << 34) | 2;
+        assert_eq!((2, 1), read_timestamp(&packed.to_be_bytes()).unwrap());
+
+        // timestamp 96: 4-byte nanos followed by an 8-byte signed seconds field.
+        let mut data = Vec::new();
+        data.extend_from_slice(&3u32.to_be_bytes());
+        data.extend_from_slice(&4i64.to_be_bytes());
+        assert_eq!((4, 3), read_timestamp(&data).unwrap());
+    }
+
+    #[test]
+    fn read_timestamp_rejects_a_payload_of_the_wrong_length() {
+        let res = read_timestamp(&[0x00, 0x00, 0x00]);
+
+        match res {
+            Err(ref err) => match *err.code() {
+                ErrorCode::InvalidTimestamp => {}
+                ref other => panic!("expected InvalidTimestamp, got {:?}", other),
+            },
+            Ok(ref val) => panic!("expected an error, got {:?}", val),
+        }
+    }
+
+    #[test]
+    fn error_position_points_at_the_byte_offset_of_the_failing_read() {
+        // FixArray(2) holding one valid element (FixPos 1), then the stream cuts off before the
+        // second element's marker byte.
+        let buf = [fixarray(2), 0x01];
+
+        let err = read_value_with_limits(&mut &buf[..], DEFAULT_MAX_DEPTH, DEFAULT_MAX_PREALLOC)
+            .unwrap_err();
+
+        assert_eq!(2, err.position());
+    }
+}
@@ -0,0 +1,261 @@
+use std::cmp;
+use std::io::{self, Cursor};
+use std::mem;
+use std::str::from_utf8;
+
+use rmp::Marker;
+use rmp::decode::{read_marker, read_data_u8, read_data_u16, read_data_u32, read_data_u64,
+                  read_data_i8, read_data_i16, read_data_i32, read_data_i64, read_data_f32,
+                  read_data_f64};
+
+use {Utf8StringRef, ValueRef};
+
+use super::value::{next_depth, read_timestamp, Error, ErrorCode, DEFAULT_MAX_DEPTH,
+                    DEFAULT_MAX_PREALLOC, EXT_TYPE_TIMESTAMP};
+
+/// Attempts to read bytes from the given slice and interpret them as a `ValueRef`, without
+/// copying any str/bin/ext bodies out of `buf`.
+///
+/// On success, returns the decoded `ValueRef` borrowing from `buf` along with the unconsumed
+/// tail of the slice, so callers can decode a stream of concatenated values without any
+/// intermediate `Read` wrapper.
+///
+/// # Errors
+///
+/// This function will return `Error` if `buf` doesn't hold a complete, well-formed value, or if
+/// arrays/maps are nested more than `DEFAULT_MAX_DEPTH` levels deep. The returned `Error` carries
+/// the byte offset of the failure, available via `Error::position`.
+pub fn read_value_ref<'a>(buf: &'a [u8]) -> Result<(ValueRef<'a>, &'a [u8]), Error> {
+    let mut cur = Cursor::new(buf);
+
+    let val = read_value_ref_with_depth(&mut cur, 0, DEFAULT_MAX_DEPTH)
+        .map_err(|err| err.at(cur.position()))?;
+
+    let pos = cur.position() as usize;
+    Ok((val, &buf[pos..]))
+}
+
+/// Slices `len` bytes out of the unread portion of `cur` without copying, advancing the cursor
+/// past them.
+fn take<'a>(cur: &mut Cursor<&'a [u8]>, len: usize) -> Result<&'a [u8], Error> {
+    let pos = cur.position() as usize;
+    let buf = *cur.get_ref();
+
+    let end = pos.checked_add(len).filter(|&end| end <= buf.len())
+        .ok_or_else(|| Error::new(ErrorCode::InvalidDataRead(io::Error::from(io::ErrorKind::UnexpectedEof))))?;
+
+    cur.set_position(end as u64);
+    Ok(&buf[pos..end])
+}
+
+fn read_str_ref<'a>(cur: &mut Cursor<&'a [u8]>, len: usize) -> Result<Utf8StringRef<'a>, Error> {
+    let bytes = take(cur, len)?;
+
+    match from_utf8(bytes) {
+        Ok(s) => Ok(Utf8StringRef { s: Ok(s) }),
+        Err(err) => Ok(Utf8StringRef { s: Err((bytes, err)) }),
+    }
+}
+
+fn read_ext_ref<'a>(cur: &mut Cursor<&'a [u8]>, len: usize) -> Result<(i8, &'a [u8]), Error> {
+    let ty = read_data_i8(cur)?;
+    let bytes = take(cur, len)?;
+
+    Ok((ty, bytes))
+}
+
+fn ext_to_value_ref<'a>(ty: i8, data: &'a [u8]) -> Result<ValueRef<'a>, Error> {
+    if ty == EXT_TYPE_TIMESTAMP {
+        let (seconds, nanos) = read_timestamp(data)?;
+        Ok(ValueRef::Timestamp { seconds: seconds, nanos: nanos })
+    } else {
+        Ok(ValueRef::Ext(ty, data))
+    }
+}
+
+fn read_array_ref<'a>(cur: &mut Cursor<&'a [u8]>, mut len: usize, depth: usize, max_depth: usize)
+    -> Result<Vec<ValueRef<'a>>, Error>
+{
+    let cap = cmp::min(len, DEFAULT_MAX_PREALLOC / cmp::max(1, mem::size_of::<ValueRef<'static>>()));
+    let mut vec = Vec::with_capacity(cap);
+
+    while len > 0 {
+        vec.push(read_value_ref_with_depth(cur, depth, max_depth)?);
+        len -= 1;
+    }
+
+    Ok(vec)
+}
+
+fn read_map_ref<'a>(cur: &mut Cursor<&'a [u8]>, mut len: usize, depth: usize, max_depth: usize)
+    -> Result<Vec<(ValueRef<'a>, ValueRef<'a>)>, Error>
+{
+    let cap = cmp::min(len, DEFAULT_MAX_PREALLOC / cmp::max(1, mem::size_of::<(ValueRef<'static>, ValueRef<'static>)>()));
+    let mut vec = Vec::with_capacity(cap);
+
+    while len > 0 {
+        vec.push((read_value_ref_with_depth(cur, depth, max_depth)?,
+                   read_value_ref_with_depth(cur, depth, max_depth)?));
+        len -= 1;
+    }
+
+    Ok(vec)
+}
+
+fn read_value_ref_with_depth<'a>(cur: &mut Cursor<&'a [u8]>, depth: usize, max_depth: usize)
+    -> Result<ValueRef<'a>, Error>
+{
+    let val = match read_marker(cur)? {
+        Marker::Null => ValueRef::Nil,
+        Marker::True => ValueRef::Boolean(true),
+        Marker::False => ValueRef::Boolean(false),
+        Marker::FixPos(val) => ValueRef::from(val),
+        Marker::FixNeg(val) => ValueRef::from(val),
+        Marker::U8 => ValueRef::from(read_data_u8(cur)?),
+        Marker::U16 => ValueRef::from(read_data_u16(cur)?),
+        Marker::U32 => ValueRef::from(read_data_u32(cur)?),
+        Marker::U64 => ValueRef::from(read_data_u64(cur)?),
+        Marker::I8 => ValueRef::from(read_data_i8(cur)?),
+        Marker::I16 => ValueRef::from(read_data_i16(cur)?),
+        Marker::I32 => ValueRef::from(read_data_i32(cur)?),
+        Marker::I64 => ValueRef::from(read_data_i64(cur)?),
+        Marker::F32 => ValueRef::F32(read_data_f32(cur)?),
+        Marker::F64 => ValueRef::F64(read_data_f64(cur)?),
+        Marker::FixStr(len) => ValueRef::String(read_str_ref(cur, len as usize)?),
+        Marker::Str8 => {
+            let len = read_data_u8(cur)?;
+            ValueRef::String(read_str_ref(cur, len as usize)?)
+        }
+        Marker::Str16 => {
+            let len = read_data_u16(cur)?;
+            ValueRef::String(read_str_ref(cur, len as usize)?)
+        }
+        Marker::Str32 => {
+            let len = read_data_u32(cur)?;
+            ValueRef::String(read_str_ref(cur, len as usize)?)
+        }
+        Marker::FixArray(len) => {
+            let vec = read_array_ref(cur, len as usize, next_depth(depth, max_depth)?, max_depth)?;
+            ValueRef::Array(vec)
+        }
+        Marker::Array16 => {
+            let len = read_data_u16(cur)?;
+            let vec = read_array_ref(cur, len as usize, next_depth(depth, max_depth)?, max_depth)?;
+            ValueRef::Array(vec)
+        }
+        Marker::Array32 => {
+            let len = read_data_u32(cur)?;
+            let vec = read_array_ref(cur, len as usize, next_depth(depth, max_depth)?, max_depth)?;
+            ValueRef::Array(vec)
+        }
+        Marker::FixMap(len) => {
+            let map = read_map_ref(cur, len as usize, next_depth(depth, max_depth)?, max_depth)?;
+            ValueRef::Map(map)
+        }
+        Marker::Map16 => {
+            let len = read_data_u16(cur)?;
+            let map = read_map_ref(cur, len as usize, next_depth(depth, max_depth)?, max_depth)?;
+            ValueRef::Map(map)
+        }
+        Marker::Map32 => {
+            let len = read_data_u32(cur)?;
+            let map = read_map_ref(cur, len as usize, next_depth(depth, max_depth)?, max_depth)?;
+            ValueRef::Map(map)
+        }
+        Marker::Bin8 => {
+            let len = read_data_u8(cur)?;
+            ValueRef::Binary(take(cur, len as usize)?)
+        }
+        Marker::Bin16 => {
+            let len = read_data_u16(cur)?;
+            ValueRef::Binary(take(cur, len as usize)?)
+        }
+        Marker::Bin32 => {
+            let len = read_data_u32(cur)?;
+            ValueRef::Binary(take(cur, len as usize)?)
+        }
+        Marker::FixExt1 => {
+            let (ty, data) = read_ext_ref(cur, 1)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::FixExt2 => {
+            let (ty, data) = read_ext_ref(cur, 2)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::FixExt4 => {
+            let (ty, data) = read_ext_ref(cur, 4)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::FixExt8 => {
+            let (ty, data) = read_ext_ref(cur, 8)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::FixExt16 => {
+            let (ty, data) = read_ext_ref(cur, 16)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::Ext8 => {
+            let len = read_data_u8(cur)? as usize;
+            let (ty, data) = read_ext_ref(cur, len)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::Ext16 => {
+            let len = read_data_u16(cur)? as usize;
+            let (ty, data) = read_ext_ref(cur, len)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::Ext32 => {
+            let len = read_data_u32(cur)? as usize;
+            let (ty, data) = read_ext_ref(cur, len)?;
+            ext_to_value_ref(ty, data)?
+        }
+        Marker::Reserved => ValueRef::Nil,
+    };
+
+    Ok(val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Value;
+
+    #[test]
+    fn read_value_ref_round_trips_and_returns_the_unconsumed_tail() {
+        // FixStr "hi" followed by an unrelated trailing byte that should be left unconsumed.
+        let buf = [0xa2, b'h', b'i', 0xff];
+
+        let (val, rest) = read_value_ref(&buf).unwrap();
+
+        match val {
+            ValueRef::String(ref s) => assert_eq!(Some("hi"), s.as_str()),
+            ref other => panic!("expected ValueRef::String, got {:?}", other),
+        }
+        assert_eq!(&[0xff], rest);
+    }
+
+    #[test]
+    fn read_value_ref_errors_on_a_truncated_slice() {
+        // Bin8 claiming 4 bytes, with only 1 actually present.
+        let buf = [0xc4, 0x04, 0x00];
+
+        let res = read_value_ref(&buf);
+
+        match res {
+            Err(ref err) => match *err.code() {
+                ErrorCode::InvalidDataRead(..) => {}
+                ref other => panic!("expected InvalidDataRead, got {:?}", other),
+            },
+            Ok(ref val) => panic!("expected a read error, got {:?}", val),
+        }
+    }
+
+    #[test]
+    fn value_ref_to_owned_copies_borrowed_bodies_into_a_value() {
+        let buf = [0xa2, b'h', b'i'];
+
+        let (val, _) = read_value_ref(&buf).unwrap();
+
+        assert_eq!(Value::String("hi".into()), val.to_owned());
+    }
+}
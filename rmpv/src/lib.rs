@@ -0,0 +1,8 @@
+//! An implementation of MessagePack value representations.
+
+extern crate rmp;
+
+pub mod decode;
+mod value;
+
+pub use value::{Utf8String, Utf8StringRef, Value, ValueRef};
@@ -0,0 +1,216 @@
+use std::str::Utf8Error;
+
+/// Represents any valid MessagePack value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// Nil represents nil.
+    Nil,
+    /// Boolean represents true or false.
+    Boolean(bool),
+    /// An unsigned integer value.
+    U64(u64),
+    /// A signed integer value.
+    I64(i64),
+    /// A 32-bit floating point number.
+    F32(f32),
+    /// A 64-bit floating point number.
+    F64(f64),
+    /// A UTF-8 string, or the raw bytes and error if the string wasn't valid UTF-8.
+    String(Utf8String),
+    /// A byte array.
+    Binary(Vec<u8>),
+    /// An array of `Value`s.
+    Array(Vec<Value>),
+    /// A map of `Value` to `Value`.
+    Map(Vec<(Value, Value)>),
+    /// An application-specific extension type, holding the type tag and its raw payload.
+    Ext(i8, Vec<u8>),
+    /// A MessagePack timestamp extension value, decoded into seconds and nanoseconds.
+    Timestamp {
+        /// Seconds since the Unix epoch.
+        seconds: i64,
+        /// The nanosecond component, as read from the payload; the decoder does not reject values
+        /// of `1_000_000_000` or more.
+        nanos: u32,
+    },
+}
+
+/// A String or a sequence of bytes that failed to decode as valid UTF-8.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Utf8String {
+    pub(crate) s: Result<String, (Vec<u8>, Utf8Error)>,
+}
+
+impl Utf8String {
+    /// Returns the string slice if the underlying bytes were valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        match self.s {
+            Ok(ref s) => Some(s.as_str()),
+            Err(..) => None,
+        }
+    }
+
+    /// Returns the raw bytes, regardless of whether they were valid UTF-8.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.s {
+            Ok(ref s) => s.as_bytes(),
+            Err((ref bytes, ..)) => bytes,
+        }
+    }
+}
+
+impl From<String> for Utf8String {
+    fn from(val: String) -> Self {
+        Utf8String { s: Ok(val) }
+    }
+}
+
+impl<'a> From<&'a str> for Utf8String {
+    fn from(val: &'a str) -> Self {
+        Utf8String { s: Ok(val.into()) }
+    }
+}
+
+macro_rules! impl_value_from_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(val: $ty) -> Value {
+                    Value::U64(val as u64)
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! impl_value_from_signed {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(val: $ty) -> Value {
+                    Value::I64(val as i64)
+                }
+            }
+        )*
+    }
+}
+
+impl_value_from_unsigned!(u8, u16, u32, u64);
+impl_value_from_signed!(i8, i16, i32, i64);
+
+/// Represents any valid MessagePack value that borrows its str/bin/ext bodies out of the buffer
+/// it was decoded from, rather than owning them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    /// Nil represents nil.
+    Nil,
+    /// Boolean represents true or false.
+    Boolean(bool),
+    /// An unsigned integer value.
+    U64(u64),
+    /// A signed integer value.
+    I64(i64),
+    /// A 32-bit floating point number.
+    F32(f32),
+    /// A 64-bit floating point number.
+    F64(f64),
+    /// A UTF-8 string, or the raw bytes and error if the string wasn't valid UTF-8.
+    String(Utf8StringRef<'a>),
+    /// A byte array, borrowed from the decode buffer.
+    Binary(&'a [u8]),
+    /// An array of `ValueRef`s.
+    Array(Vec<ValueRef<'a>>),
+    /// A map of `ValueRef` to `ValueRef`.
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    /// An application-specific extension type, holding the type tag and its raw payload.
+    Ext(i8, &'a [u8]),
+    /// A MessagePack timestamp extension value, decoded into seconds and nanoseconds.
+    Timestamp {
+        /// Seconds since the Unix epoch.
+        seconds: i64,
+        /// The nanosecond component, as read from the payload; the decoder does not reject values
+        /// of `1_000_000_000` or more.
+        nanos: u32,
+    },
+}
+
+impl<'a> ValueRef<'a> {
+    /// Converts this `ValueRef` into an owned `Value`, copying any borrowed str/bin/ext bodies.
+    pub fn to_owned(&self) -> Value {
+        match *self {
+            ValueRef::Nil => Value::Nil,
+            ValueRef::Boolean(val) => Value::Boolean(val),
+            ValueRef::U64(val) => Value::U64(val),
+            ValueRef::I64(val) => Value::I64(val),
+            ValueRef::F32(val) => Value::F32(val),
+            ValueRef::F64(val) => Value::F64(val),
+            ValueRef::String(ref val) => Value::String(val.to_owned()),
+            ValueRef::Binary(val) => Value::Binary(val.to_vec()),
+            ValueRef::Array(ref vec) => Value::Array(vec.iter().map(ValueRef::to_owned).collect()),
+            ValueRef::Map(ref vec) => {
+                Value::Map(vec.iter().map(|&(ref k, ref v)| (k.to_owned(), v.to_owned())).collect())
+            }
+            ValueRef::Ext(ty, data) => Value::Ext(ty, data.to_vec()),
+            ValueRef::Timestamp { seconds, nanos } => Value::Timestamp { seconds: seconds, nanos: nanos },
+        }
+    }
+}
+
+/// A borrowed `&str`, or the raw bytes and error if they weren't valid UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Utf8StringRef<'a> {
+    pub(crate) s: Result<&'a str, (&'a [u8], Utf8Error)>,
+}
+
+impl<'a> Utf8StringRef<'a> {
+    /// Returns the string slice if the underlying bytes were valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.s {
+            Ok(s) => Some(s),
+            Err(..) => None,
+        }
+    }
+
+    /// Returns the raw bytes, regardless of whether they were valid UTF-8.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self.s {
+            Ok(s) => s.as_bytes(),
+            Err((bytes, ..)) => bytes,
+        }
+    }
+
+    /// Converts this borrowed string into an owned `Utf8String`.
+    pub fn to_owned(&self) -> Utf8String {
+        match self.s {
+            Ok(s) => Utf8String { s: Ok(s.to_owned()) },
+            Err((bytes, err)) => Utf8String { s: Err((bytes.to_vec(), err)) },
+        }
+    }
+}
+
+macro_rules! impl_valueref_from_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> From<$ty> for ValueRef<'a> {
+                fn from(val: $ty) -> ValueRef<'a> {
+                    ValueRef::U64(val as u64)
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! impl_valueref_from_signed {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> From<$ty> for ValueRef<'a> {
+                fn from(val: $ty) -> ValueRef<'a> {
+                    ValueRef::I64(val as i64)
+                }
+            }
+        )*
+    }
+}
+
+impl_valueref_from_unsigned!(u8, u16, u32, u64);
+impl_valueref_from_signed!(i8, i16, i32, i64);